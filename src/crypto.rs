@@ -0,0 +1,184 @@
+//! Firmware integrity and authenticity checks: a detached Ed25519 signature, verified over the
+//! image bytes exactly as read from the file (unpadded, signature block removed) before
+//! `Program` is allowed to erase anything. The signature is not checked against a SHA-256
+//! hash; `sha256` only hashes the padded image for the on-device `Verify` request.
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::Path;
+
+/// Ed25519 signatures are a fixed 64 bytes.
+const SIGNATURE_LEN: usize = 64;
+
+/// Public key baked into the client so a bare `stm32client program foo.bin --signature foo.sig`
+/// verifies against a known-good signer without requiring `--pubkey` on every invocation.
+/// Override with `--pubkey <file>` for images signed by a different key.
+const TRUSTED_PUBLIC_KEY_HEX: &str =
+    "8724327beeda8d4fd7925589ed7e04a1d3203c222b20039cf79c668ed33f4a24";
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// Decodes a compact hex-encoded Ed25519 public key, either from `--pubkey <file>` or the
+/// key embedded in the client.
+pub fn load_public_key(pubkey_file: Option<&Path>) -> io::Result<VerifyingKey> {
+    let encoded = match pubkey_file {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => TRUSTED_PUBLIC_KEY_HEX.to_string(),
+    };
+
+    let bytes = decode_hex(encoded.trim())?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Public key must be 32 bytes"))?;
+
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Malformed Ed25519 public key"))
+}
+
+/// Reads a detached signature, either from `--signature <file>` (hex-encoded) or from a
+/// 64-byte block appended to the end of the firmware image itself.
+pub fn extract_signature<'a>(
+    image: &'a [u8],
+    signature_file: Option<&Path>,
+) -> io::Result<(&'a [u8], Signature)> {
+    if let Some(path) = signature_file {
+        let encoded = std::fs::read_to_string(path)?;
+        let bytes = decode_hex(encoded.trim())?;
+        return Ok((image, decode_signature(&bytes)?));
+    }
+
+    if image.len() < SIGNATURE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Image is too short to contain an appended signature",
+        ));
+    }
+
+    let (payload, tail) = image.split_at(image.len() - SIGNATURE_LEN);
+    Ok((payload, decode_signature(tail)?))
+}
+
+fn decode_signature(bytes: &[u8]) -> io::Result<Signature> {
+    let bytes: [u8; SIGNATURE_LEN] = bytes
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Signature must be 64 bytes"))?;
+    Ok(Signature::from_bytes(&bytes))
+}
+
+/// Verifies `payload` against `signature` using `pubkey`. Returns an error rather than a bare
+/// `bool` so callers can report *why* verification failed.
+pub fn verify(payload: &[u8], pubkey: &VerifyingKey, signature: &Signature) -> io::Result<()> {
+    pubkey
+        .verify(payload, signature)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Firmware signature is invalid"))
+}
+
+fn decode_hex(s: &str) -> io::Result<Vec<u8>> {
+    if !s.is_ascii() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Hex string must be ASCII",
+        ));
+    }
+
+    if s.len() % 2 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Hex string has an odd number of digits",
+        ));
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid hex digit"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PUBLIC_KEY_HEX: &str =
+        "ed011784e2e7020328d084656e73d6b0ed4660a538f3e604c0ac8a2a6f919d93";
+    const TEST_MESSAGE: &[u8] = b"hello firmware";
+    const TEST_SIGNATURE_HEX: &str = "20477888e1d0b7d3f1105e24a40d8f1c06978616861dac7948844b4e798e90be42e46e747a91f4b62f5dc443493061f5b67fb2cbdad2d5c3197cd597d2e6d402";
+
+    fn test_pubkey() -> VerifyingKey {
+        let bytes: [u8; 32] = decode_hex(TEST_PUBLIC_KEY_HEX)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        VerifyingKey::from_bytes(&bytes).unwrap()
+    }
+
+    fn test_signature() -> Signature {
+        decode_signature(&decode_hex(TEST_SIGNATURE_HEX).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn trusted_public_key_loads() {
+        load_public_key(None).expect("embedded public key should be valid");
+    }
+
+    #[test]
+    fn decode_hex_round_trips_known_bytes() {
+        assert_eq!(decode_hex("deadbeef").unwrap(), vec![0xDE, 0xAD, 0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn decode_hex_rejects_odd_length() {
+        assert!(decode_hex("abc").is_err());
+    }
+
+    #[test]
+    fn decode_hex_rejects_invalid_digits() {
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    fn decode_hex_rejects_non_ascii_without_panicking() {
+        assert!(decode_hex("föo").is_err());
+    }
+
+    #[test]
+    fn decode_signature_rejects_wrong_length() {
+        assert!(decode_signature(&[0u8; 32]).is_err());
+    }
+
+    #[test]
+    fn extract_signature_splits_appended_block() {
+        let mut image = TEST_MESSAGE.to_vec();
+        image.extend_from_slice(&decode_hex(TEST_SIGNATURE_HEX).unwrap());
+
+        let (payload, signature) =
+            extract_signature(&image, None).expect("appended signature should parse");
+
+        assert_eq!(payload, TEST_MESSAGE);
+        assert_eq!(signature, test_signature());
+    }
+
+    #[test]
+    fn extract_signature_rejects_image_too_short_for_appended_signature() {
+        let image = vec![0u8; SIGNATURE_LEN - 1];
+        assert!(extract_signature(&image, None).is_err());
+    }
+
+    #[test]
+    fn verify_accepts_genuine_signature() {
+        verify(TEST_MESSAGE, &test_pubkey(), &test_signature()).expect("signature is genuine");
+    }
+
+    #[test]
+    fn verify_rejects_tampered_payload() {
+        let tampered = b"hello firmwarX";
+        assert!(verify(tampered, &test_pubkey(), &test_signature()).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Parses a firmware image — raw `.bin`, Intel HEX, or ELF — into the loadable segments
+//! (flash address + bytes) that need to be erased and written. `.bin` has no address
+//! information of its own, so it's treated as a single segment loaded at `--lma`; `.hex`
+//! and `.elf` carry real per-region addresses that are honored directly.
+
+use std::io;
+
+use ihex::Record;
+use object::{Object, ObjectSegment};
+
+/// The first address past the bootloader itself; this is where `flash_bank1_len` and
+/// `flash_bank2_len` from `ReadResponse` are measured from.
+pub const USER_FLASH_BASE: u32 = 0x0801_0000;
+
+/// One contiguous region of firmware bytes targeted at a specific flash address.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// Parses an already-read firmware image, dispatching on `extension`, and merges any
+/// segments that turned out to be back-to-back in flash. Takes the image as bytes rather
+/// than a path so the caller can verify it (and strip any appended signature block) before
+/// these are the bytes that actually get erased-for and flashed.
+pub fn load_bytes(data: &[u8], extension: Option<&str>, lma: u64) -> io::Result<Vec<Segment>> {
+    let segments = match extension {
+        Some("hex") | Some("ihex") => load_ihex(data)?,
+        Some("elf") => load_elf(data)?,
+        _ => vec![Segment {
+            address: lma as u32,
+            data: data.to_vec(),
+        }],
+    };
+
+    Ok(merge_contiguous(segments))
+}
+
+fn load_ihex(data: &[u8]) -> io::Result<Vec<Segment>> {
+    let text = std::str::from_utf8(data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut segments = Vec::new();
+    let mut upper_linear_address: u32 = 0;
+
+    for record in ihex::Reader::new(text) {
+        let record =
+            record.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        match record {
+            Record::Data { offset, value } => {
+                let address = upper_linear_address | offset as u32;
+                segments.push(Segment {
+                    address,
+                    data: value,
+                });
+            }
+            Record::ExtendedLinearAddress(high) => {
+                upper_linear_address = (high as u32) << 16;
+            }
+            Record::EndOfFile => break,
+            _ => {}
+        }
+    }
+
+    Ok(segments)
+}
+
+fn load_elf(bytes: &[u8]) -> io::Result<Vec<Segment>> {
+    let file = object::File::parse(bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    let mut segments = Vec::new();
+    for segment in file.segments() {
+        let data = segment
+            .data()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        if data.is_empty() {
+            continue;
+        }
+
+        segments.push(Segment {
+            address: segment.address() as u32,
+            data: data.to_vec(),
+        });
+    }
+
+    Ok(segments)
+}
+
+/// Merges segments whose address ranges are back-to-back into one, so a HEX/ELF file
+/// emitted as several adjacent chunks doesn't turn into many tiny erase/write sessions.
+fn merge_contiguous(mut segments: Vec<Segment>) -> Vec<Segment> {
+    segments.sort_by_key(|segment| segment.address);
+
+    let mut merged: Vec<Segment> = Vec::new();
+    for segment in segments {
+        if let Some(last) = merged.last_mut() {
+            let last_end = last.address as u64 + last.data.len() as u64;
+            if last_end == segment.address as u64 {
+                last.data.extend_from_slice(&segment.data);
+                continue;
+            }
+        }
+        merged.push(segment);
+    }
+
+    merged
+}
+
+/// Confirms every segment lands entirely within one of the device's two reported flash
+/// banks, measured from `USER_FLASH_BASE`.
+pub fn validate_against_banks(
+    segments: &[Segment],
+    bank1_len: u32,
+    bank2_len: u32,
+) -> io::Result<()> {
+    let bank1 = USER_FLASH_BASE..USER_FLASH_BASE + bank1_len;
+    let bank2 = bank1.end..bank1.end + bank2_len;
+
+    for segment in segments {
+        let range = segment.address..segment.address + segment.data.len() as u32;
+        let fits_bank1 = range.start >= bank1.start && range.end <= bank1.end;
+        let fits_bank2 = range.start >= bank2.start && range.end <= bank2.end;
+
+        if !fits_bank1 && !fits_bank2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Segment at 0x{:08x} (len {}) does not fit in any reported flash bank",
+                    segment.address,
+                    segment.data.len()
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_contiguous_joins_back_to_back_segments() {
+        let segments = vec![
+            Segment {
+                address: 0x1000,
+                data: vec![0xAA; 16],
+            },
+            Segment {
+                address: 0x1010,
+                data: vec![0xBB; 16],
+            },
+        ];
+
+        let merged = merge_contiguous(segments);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].address, 0x1000);
+        assert_eq!(merged[0].data.len(), 32);
+    }
+
+    #[test]
+    fn merge_contiguous_keeps_gapped_segments_separate() {
+        let segments = vec![
+            Segment {
+                address: 0x1000,
+                data: vec![0xAA; 16],
+            },
+            Segment {
+                address: 0x2000,
+                data: vec![0xBB; 16],
+            },
+        ];
+
+        let merged = merge_contiguous(segments);
+
+        assert_eq!(merged.len(), 2);
+    }
+
+    #[test]
+    fn merge_contiguous_sorts_out_of_order_input() {
+        let segments = vec![
+            Segment {
+                address: 0x1010,
+                data: vec![0xBB; 16],
+            },
+            Segment {
+                address: 0x1000,
+                data: vec![0xAA; 16],
+            },
+        ];
+
+        let merged = merge_contiguous(segments);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].address, 0x1000);
+    }
+
+    #[test]
+    fn load_bytes_with_no_extension_is_one_raw_segment_at_lma() {
+        let data = vec![1, 2, 3, 4];
+        let segments = load_bytes(&data, None, 0x0801_0000).expect("raw image should parse");
+
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].address, 0x0801_0000);
+        assert_eq!(segments[0].data, data);
+    }
+
+    #[test]
+    fn load_ihex_parses_data_records_with_extended_linear_address() {
+        let hex = ":020000040801F1\r\n:04000000DEADBEEFC4\r\n:00000001FF\r\n";
+
+        let segments = load_ihex(hex.as_bytes()).expect("well-formed ihex should parse");
+
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].address, 0x0801_0000);
+        assert_eq!(segments[0].data, vec![0xDE, 0xAD, 0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn load_ihex_rejects_malformed_records() {
+        let hex = ":not-a-valid-record\r\n";
+
+        assert!(load_ihex(hex.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn validate_against_banks_accepts_segment_inside_bank1() {
+        let segments = vec![Segment {
+            address: USER_FLASH_BASE,
+            data: vec![0u8; 64],
+        }];
+
+        validate_against_banks(&segments, 128, 128).expect("segment fits in bank1");
+    }
+
+    #[test]
+    fn validate_against_banks_accepts_segment_inside_bank2() {
+        let segments = vec![Segment {
+            address: USER_FLASH_BASE + 128,
+            data: vec![0u8; 64],
+        }];
+
+        validate_against_banks(&segments, 128, 128).expect("segment fits in bank2");
+    }
+
+    #[test]
+    fn validate_against_banks_rejects_segment_past_both_banks() {
+        let segments = vec![Segment {
+            address: USER_FLASH_BASE + 256,
+            data: vec![0u8; 64],
+        }];
+
+        assert!(validate_against_banks(&segments, 128, 128).is_err());
+    }
+
+    #[test]
+    fn validate_against_banks_rejects_segment_straddling_bank_boundary() {
+        let segments = vec![Segment {
+            address: USER_FLASH_BASE + 96,
+            data: vec![0u8; 64],
+        }];
+
+        assert!(validate_against_banks(&segments, 128, 128).is_err());
+    }
+}
@@ -1,13 +1,19 @@
 use std::{
     io::{Read, Write},
-    net::TcpStream,
+    net::{TcpStream, UdpSocket},
     path::PathBuf,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 
+mod crypto;
+mod firmware;
+mod transport;
+
+use transport::{Transport, TransportConfig, TransportKind};
+
 #[derive(Serialize, Deserialize)]
 enum Command<'a> {
     Info,
@@ -15,6 +21,9 @@ enum Command<'a> {
     Erase { address: u32, length: u32 },
     Write { sector: &'a [u8], data: &'a [u8] },
     Boot,
+    /// Asks the device to re-hash what it actually wrote to flash and compare it against
+    /// the hash of the image that was sent, catching silent write corruption.
+    Verify { hash: [u8; 32] },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,13 +55,25 @@ pub struct ReadResponse<'a> {
 #[derive(Parser, Debug)]
 #[command(name = "Bootloader Client", version = "0.1.0")]
 struct Cli {
-    /// IP address/hostname of bootloader
-    hostname: String,
+    /// IP address/hostname of bootloader, required for `--transport tcp` (the default)
+    hostname: Option<String>,
 
     /// Bootloader port, default 7777
     #[arg(long, default_value_t = 6971)]
     port: u16,
 
+    /// Transport used to reach the bootloader
+    #[arg(long, value_enum, default_value = "tcp")]
+    transport: TransportKind,
+
+    /// Serial device to use with `--transport serial`, e.g. /dev/ttyUSB0
+    #[arg(long)]
+    device: Option<String>,
+
+    /// Baud rate for `--transport serial`
+    #[arg(long, default_value_t = 115_200)]
+    baud: u32,
+
     /// Send an initial boot request to user firmware
     #[arg(long)]
     boot_req: bool,
@@ -73,10 +94,33 @@ struct Cli {
     #[arg(long, default_value_t = 200)]
     timeout: u64,
 
+    /// Number of times to reconnect and resume a flash after a dropped connection
+    #[arg(long, default_value_t = 5)]
+    max_retries: u32,
+
+    /// Cap flash write throughput to this many bytes/sec, useful for slow bootloader
+    /// flash controllers that can't keep up with a full-speed link
+    #[arg(long, value_parser = parse_rate_limit)]
+    rate_limit: Option<u64>,
+
+    /// Number of `Write` commands to keep outstanding at once, default 1 (no pipelining)
+    #[arg(long, default_value_t = 1)]
+    window: u32,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Rejects `--rate-limit 0`, which would make `throttle` divide by zero and panic on the
+/// first confirmed chunk.
+fn parse_rate_limit(s: &str) -> Result<u64, String> {
+    let value: u64 = s.parse().map_err(|_| format!("`{s}` is not a valid number"))?;
+    if value == 0 {
+        return Err("must be greater than 0".to_string());
+    }
+    Ok(value)
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Just read bootloader information without rebooting
@@ -88,7 +132,26 @@ enum Commands {
         #[arg(long, default_value_t = 0x08010000)]
         lma: u64,
 
-        /// Raw binary file to program
+        /// Continue a previously interrupted flash without erasing: device info is queried to
+        /// confirm the image layout, then the whole image is written again from its first sector
+        #[arg(long)]
+        resume: bool,
+
+        /// Trusted Ed25519 public key to verify the image against, hex-encoded in a file.
+        /// Defaults to the public key embedded in the client.
+        #[arg(long)]
+        pubkey: Option<PathBuf>,
+
+        /// Detached, hex-encoded Ed25519 signature of the image. If omitted, the last 64
+        /// bytes of `binfile` are treated as an appended signature block.
+        #[arg(long)]
+        signature: Option<PathBuf>,
+
+        /// Ask the device to re-check the hash of what it wrote after flashing completes
+        #[arg(long)]
+        verify_on_device: bool,
+
+        /// Firmware image to program: raw `.bin` (loaded at `--lma`), Intel `.hex`, or `.elf`
         binfile: PathBuf,
     },
 
@@ -116,57 +179,334 @@ enum Commands {
     Erase,
 }
 
+/// Renders a live progress bar with current throughput and estimated time remaining,
+/// overwriting the previous line.
+fn print_progress(bytes_written: u64, total_bytes: u64, start: Instant) {
+    let elapsed = start.elapsed().as_secs_f64().max(0.001);
+    let throughput = bytes_written as f64 / elapsed;
+    let fraction = bytes_written as f64 / total_bytes.max(1) as f64;
+
+    let bar_width = 30;
+    let filled = (fraction * bar_width as f64).round() as usize;
+    let bar: String = "#".repeat(filled) + &"-".repeat(bar_width - filled);
+
+    let remaining_bytes = total_bytes.saturating_sub(bytes_written) as f64;
+    let eta_secs = if throughput > 0.0 {
+        remaining_bytes / throughput
+    } else {
+        0.0
+    };
+
+    print!(
+        "\r[{}] {:>5.1}%  {:>7.1} KB/s  ETA {:>5.1}s",
+        bar,
+        fraction * 100.0,
+        throughput / 1024.0,
+        eta_secs
+    );
+    std::io::stdout().flush().ok();
+}
+
+/// Sleeps just long enough that cumulative throughput since `start` doesn't exceed
+/// `rate_limit` bytes/sec.
+fn throttle(rate_limit: u64, start: Instant, bytes_written: u64) {
+    let expected = Duration::from_secs_f64(bytes_written as f64 / rate_limit as f64);
+    let elapsed = start.elapsed();
+    if expected > elapsed {
+        std::thread::sleep(expected - elapsed);
+    }
+}
+
+/// A device sitting in user firmware needs a UDP nudge to drop into the bootloader before
+/// the TCP session can be opened. Sends a boot-request datagram, retrying a few times since
+/// UDP has no delivery guarantee, then waits for `tcp_port` to start accepting connections.
+const BOOT_REQUEST_RETRIES: u32 = 5;
+const BOOT_REQUEST_DATAGRAM: &[u8] = b"BOOTREQ";
+
+fn request_bootloader_entry(
+    hostname: &str,
+    boot_req_port: u16,
+    tcp_port: u16,
+    timeout: u64,
+) -> Result<(), std::io::Error> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(Duration::from_secs(timeout)))?;
+    socket.connect((hostname, boot_req_port))?;
+
+    let mut ack = [0u8; 64];
+    for attempt in 1..=BOOT_REQUEST_RETRIES {
+        socket.send(BOOT_REQUEST_DATAGRAM)?;
+
+        match socket.recv(&mut ack) {
+            Ok(_) => break,
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                println!(
+                    "No boot-request ack yet (attempt {}/{}), retrying",
+                    attempt, BOOT_REQUEST_RETRIES
+                );
+                if attempt == BOOT_REQUEST_RETRIES {
+                    println!("Giving up on an ack; assuming the device is rebooting anyway");
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    wait_for_tcp_port(hostname, tcp_port, timeout)
+}
+
+/// Polls `hostname:port` until a TCP connection succeeds or ten times `timeout` seconds
+/// (at least 10s) pass, since
+/// the bootloader takes a moment to come up after a UDP boot-request.
+fn wait_for_tcp_port(hostname: &str, port: u16, timeout: u64) -> Result<(), std::io::Error> {
+    let deadline = Instant::now() + Duration::from_secs(timeout.max(1) * 10);
+
+    loop {
+        match TcpStream::connect((hostname, port)) {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                if Instant::now() >= deadline {
+                    return Err(e);
+                }
+                std::thread::sleep(Duration::from_millis(500));
+            }
+        }
+    }
+}
+
+/// An `io::Error` from `Client::write_window`, carrying how many chunks at the front of
+/// that batch were already confirmed before the error hit, so the caller can advance past
+/// them instead of resending a whole window's worth of already-written sectors.
+struct WindowError {
+    confirmed: usize,
+    source: std::io::Error,
+}
+
+/// One chunk-sized piece of a `firmware::Segment`, addressed by its index within the
+/// flattened write plan so a window of them can be sent and confirmed as a unit.
+struct Chunk {
+    segment_index: usize,
+    sector: u32,
+    start: usize,
+    end: usize,
+}
+
 struct Client {
-    socket: TcpStream,
+    transport: Box<dyn Transport>,
     chunk_size: u32,
+    config: TransportConfig,
+    timeout: u64,
 }
 
 impl Client {
-    fn new(
-        hostname: &str,
-        port: u16,
+    fn connect(
+        config: TransportConfig,
         timeout: u64,
         chunk_size: u32,
     ) -> Result<Self, std::io::Error> {
-        let socket = TcpStream::connect((hostname, port))?;
-        socket.set_read_timeout(Some(Duration::from_secs(timeout)))?;
-        socket.set_write_timeout(Some(Duration::from_secs(timeout)))?;
-        //Just block for now as we only do one op at a time
-        socket.set_nonblocking(false)?;
+        let transport = config.open(timeout)?;
 
-        Ok(Self { socket, chunk_size })
+        Ok(Self {
+            transport,
+            chunk_size,
+            config,
+            timeout,
+        })
     }
 
-    fn send_program_request(&mut self, lma: u64, binfile: PathBuf) -> Result<(), std::io::Error> {
-        let mut binfile = std::fs::read(binfile).expect("Failed to read binfile");
-
-        let len = binfile.len();
-        let padding = if len % 32 == 0 { 0 } else { 32 - (len % 32) };
-        binfile.resize(len + padding, 0xFF);
-        let mut segments = binfile.chunks(self.chunk_size as usize);
+    /// Drops the current transport and reopens it, used to resync a flashing session after
+    /// a mid-transfer `io::Error` (reset, timeout, etc) on either TCP or serial.
+    fn reconnect(&mut self) -> Result<(), std::io::Error> {
+        self.transport = self.config.open(self.timeout)?;
+        Ok(())
+    }
 
-        println!("Erasing flash sector");
-        self.erase_flash(0x08010000, len as u32)?;
+    fn send_program_request(
+        &mut self,
+        lma: u64,
+        image: Vec<u8>,
+        image_extension: Option<String>,
+        max_retries: u32,
+        resume: bool,
+        rate_limit: Option<u64>,
+        verify_on_device: bool,
+        window: u32,
+    ) -> Result<(), std::io::Error> {
+        let window = window.max(1) as usize;
+
+        let segments = firmware::load_bytes(&image, image_extension.as_deref(), lma).map_err(
+            |e| std::io::Error::new(e.kind(), format!("Failed to parse firmware image: {e}")),
+        )?;
+
+        let info = self.send_info_request()?;
+        firmware::validate_against_banks(
+            &segments,
+            info.flash_bank1_len as u32,
+            info.flash_bank2_len as u32,
+        )
+        .map_err(|e| std::io::Error::new(e.kind(), e.to_string()))?;
+
+        // Pad each segment to a 32-byte boundary as the bootloader's flash writes require.
+        let segments: Vec<firmware::Segment> = segments
+            .into_iter()
+            .map(|mut segment| {
+                let len = segment.data.len();
+                let padding = if len % 32 == 0 { 0 } else { 32 - (len % 32) };
+                segment.data.resize(len + padding, 0xFF);
+                segment
+            })
+            .collect();
+
+        let total_bytes: u64 = segments.iter().map(|s| s.data.len() as u64).sum();
+
+        // Flattened (segment, chunk) pairs so resync can resume from a single global index
+        // even though the image may now span several non-contiguous flash regions.
+        let mut chunks = Vec::new();
+        for (segment_index, segment) in segments.iter().enumerate() {
+            for (sector, chunk) in segment.data.chunks(self.chunk_size as usize).enumerate() {
+                let start = sector * self.chunk_size as usize;
+                chunks.push(Chunk {
+                    segment_index,
+                    sector: sector as u32,
+                    start,
+                    end: start + chunk.len(),
+                });
+            }
+        }
 
-        let segments_len = segments.len();
+        // `resume` skips erasing altogether: every segment starts out marked as erased.
+        let mut erased = vec![resume; segments.len()];
+
+        let mut retries_left = max_retries;
+        let mut bytes_written: u64 = 0;
+        let start = Instant::now();
+
+        let mut i = 0usize;
+        while i < chunks.len() {
+            let segment_index = chunks[i].segment_index;
+            let segment = &segments[segment_index];
+
+            if !erased[segment_index] {
+                println!(
+                    "Erasing flash at 0x{:08x} ({} bytes)",
+                    segment.address,
+                    segment.data.len()
+                );
+                self.erase_flash(segment.address, segment.data.len() as u32)?;
+                erased[segment_index] = true;
+            }
+
+            // A window never spans a segment boundary, since each segment gets its own
+            // erase and its own local sector numbering.
+            let batch_end = (i..chunks.len())
+                .take_while(|&j| chunks[j].segment_index == segment_index)
+                .take(window)
+                .count()
+                + i;
+
+            let batch_len = batch_end - i;
+
+            match self.write_window(&segments, &chunks[i..batch_end]) {
+                Ok(confirmed) => {
+                    for chunk in &chunks[i..i + confirmed] {
+                        bytes_written += (chunk.end - chunk.start) as u64;
+                    }
+                    print_progress(bytes_written, total_bytes, start);
+
+                    if let Some(rate_limit) = rate_limit {
+                        throttle(rate_limit, start, bytes_written);
+                    }
+
+                    if confirmed == batch_len {
+                        i += confirmed;
+                    } else if retries_left > 0 {
+                        retries_left -= 1;
+                        println!(
+                            "\nSector {} failed, reconnecting and resuming (retries left: {})",
+                            chunks[i + confirmed].sector,
+                            retries_left
+                        );
+                        self.reconnect()?;
+                        i += confirmed;
+                    } else {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            format!("Sector {} failed with no retries remaining", chunks[i + confirmed].sector),
+                        ));
+                    }
+                }
+                Err(WindowError { confirmed, source }) if retries_left > 0 => {
+                    retries_left -= 1;
+                    for chunk in &chunks[i..i + confirmed] {
+                        bytes_written += (chunk.end - chunk.start) as u64;
+                    }
+                    print_progress(bytes_written, total_bytes, start);
+                    println!(
+                        "\nConnection error ({}) in write window starting at sector {}, reconnecting and resuming (retries left: {})",
+                        source, chunks[i + confirmed].sector, retries_left
+                    );
+                    i += confirmed;
+                    self.reconnect()?;
+                }
+                Err(e) => return Err(e.source),
+            }
+        }
 
-        for (i, segment) in segments.into_iter().enumerate() {
-            println!(
-                "Writing segment(size={}) {} of {}",
-                segment.len(),
-                i,
-                segments_len
-            );
-            self.write_flash(i as u32, segment).unwrap();
+        let elapsed = start.elapsed();
+        let throughput = bytes_written as f64 / elapsed.as_secs_f64().max(0.001);
+        println!(
+            "\nDone: {} bytes in {:.1}s ({:.1} KB/s average)",
+            bytes_written,
+            elapsed.as_secs_f64(),
+            throughput / 1024.0
+        );
+
+        if verify_on_device {
+            println!("Asking device to verify what it wrote");
+            let image: Vec<u8> = segments.into_iter().flat_map(|s| s.data).collect();
+            let hash = crypto::sha256(&image);
+            let status = self.send_verify_request(hash)?;
+            println!("Device verify status: {:?}", status);
         }
 
         Ok(())
     }
 
+    fn send_verify_request(&mut self, hash: [u8; 32]) -> Result<BootloadError, std::io::Error> {
+        let cmd = Command::Verify { hash };
+        let cmd = postcard::to_stdvec(&cmd).expect("Failed to serialize verify command");
+        self.write_frame(&cmd)?;
+        self.get_reply()
+    }
+
+    /// Writes a single frame: a little-endian `u32` byte length followed by `payload`.
+    fn write_frame(&mut self, payload: &[u8]) -> Result<(), std::io::Error> {
+        let len = payload.len() as u32;
+        self.transport.write_all(&len.to_le_bytes())?;
+        self.transport.write_all(payload)?;
+        Ok(())
+    }
+
+    /// Reads a single frame: a little-endian `u32` byte length followed by exactly that
+    /// many bytes, read with `read_exact` so a reply split across TCP segments is never
+    /// mistaken for a short one.
+    fn read_frame(&mut self) -> Result<Vec<u8>, std::io::Error> {
+        let mut len_buf = [0u8; 4];
+        self.transport.read_exact(&mut len_buf)?;
+        let len = u32::from_le_bytes(len_buf) as usize;
+
+        let mut payload = vec![0u8; len];
+        self.transport.read_exact(&mut payload)?;
+        Ok(payload)
+    }
+
     fn erase_flash(&mut self, address: u32, length: u32) -> Result<(), std::io::Error> {
         let cmd = Command::Erase { address, length };
         let cmd = postcard::to_stdvec(&cmd).expect("Failed to serialize erase command");
-        self.socket.write_all(&cmd)?;
+        self.write_frame(&cmd)?;
 
         println!("{:?} erasing the flash", self.get_reply());
 
@@ -174,67 +514,179 @@ impl Client {
     }
 
     fn get_reply(&mut self) -> Result<BootloadError, std::io::Error> {
-        let mut buf = vec![0; 256];
-        self.socket.read(&mut buf)?;
+        let buf = self.read_frame()?;
         postcard::from_bytes(&buf).map_err(|_| {
             std::io::Error::new(std::io::ErrorKind::Other, "Failed to deserialize error")
         })
     }
 
-    fn write_flash(&mut self, sector: u32, data: &[u8]) -> Result<(), std::io::Error> {
+    /// Sends one `Command::Write` frame without waiting for its reply, so a window of
+    /// several sectors can be in flight at once instead of round-tripping on every one.
+    fn send_write(&mut self, sector: u32, data: &[u8]) -> Result<(), std::io::Error> {
         let sector = &sector.to_le_bytes();
         let cmd = Command::Write { sector, data };
         let cmd = postcard::to_stdvec(&cmd).expect("Failed to serialize write command");
-        println!("Writing {} bytes to the socket", cmd.len());
-        self.socket.write_all(&cmd).unwrap();
+        self.write_frame(&cmd)
+    }
 
-        println!("Writing status: {:?}", self.get_reply());
+    /// Sends every chunk in `batch` back-to-back, then collects their replies in order,
+    /// keeping up to `batch.len()` writes outstanding at once instead of blocking on each
+    /// one's reply before sending the next. Returns the number of leading chunks confirmed
+    /// with `Success`/`PartialWriteSuccess`; a non-`io::Error` failure partway through the
+    /// batch still drains the remaining replies to keep the frame stream in sync, but stops
+    /// counting there so the caller knows exactly which sector to retry from. An `io::Error`
+    /// reading a reply aborts the drain immediately, but still reports how many chunks were
+    /// confirmed earlier in the same batch, via `WindowError::confirmed`, so the caller
+    /// doesn't have to resend sectors that already succeeded.
+    fn write_window(
+        &mut self,
+        segments: &[firmware::Segment],
+        batch: &[Chunk],
+    ) -> Result<usize, WindowError> {
+        for chunk in batch {
+            let data = &segments[chunk.segment_index].data[chunk.start..chunk.end];
+            self.send_write(chunk.sector, data)
+                .map_err(|source| WindowError { confirmed: 0, source })?;
+        }
 
-        Ok(())
+        let mut confirmed = 0;
+        let mut failed = false;
+        for chunk in batch {
+            let status = self
+                .get_reply()
+                .map_err(|source| WindowError { confirmed, source })?;
+            if failed {
+                continue;
+            }
+            match status {
+                BootloadError::Success | BootloadError::PartialWriteSuccess(_) => confirmed += 1,
+                other => {
+                    println!("\nSector {} reported {:?}", chunk.sector, other);
+                    failed = true;
+                }
+            }
+        }
+
+        Ok(confirmed)
     }
 
-    fn send_info_request(&mut self) -> Result<(), std::io::Error> {
+    fn send_info_request(&mut self) -> Result<ReadResponse<'static>, std::io::Error> {
         let cmd = Command::Info;
         let cmd = postcard::to_stdvec(&cmd).expect("Failed to serialize info command");
-        self.socket.write_all(&cmd)?;
-
-        //println!("Info reply: {:?}", self.get_reply()?);
-
-        //let mut buf = vec![0; 1024];
-        //self.socket.read_to_end(&mut buf)?;
-        //let respionse = postcard::from_bytes::<ReadResponse>(&buf)
-        //    .map(|el| el.clone())
-        //    .map_err(|_| {
-        //        std::io::Error::new(
-        //            std::io::ErrorKind::Other,
-        //            "Failed to deserialize info response",
-        //        )
-        //    })?;
-
-        //println!("Info response: {:?}", respionse);
+        self.write_frame(&cmd)?;
+
+        let buf = self.read_frame()?;
+        let response: ReadResponse = postcard::from_bytes(&buf).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Failed to deserialize info response",
+            )
+        })?;
+
+        // Leak copies of the borrowed byte fields so we can hand back a `ReadResponse<'static>`
+        // without threading a lifetime through `Client`; they are tiny and this call is rare.
+        let response = ReadResponse {
+            bootloader_version: Box::leak(response.bootloader_version.to_vec().into_boxed_slice()),
+            git_version: Box::leak(response.git_version.to_vec().into_boxed_slice()),
+            built_time: Box::leak(response.built_time.to_vec().into_boxed_slice()),
+            flash_bank1_len: response.flash_bank1_len,
+            flash_bank2_len: response.flash_bank2_len,
+        };
+
+        println!("Info response: {:?}", response);
+
+        Ok(response)
+    }
 
-        Ok(())
+    fn send_boot_request(&mut self) -> Result<(), std::io::Error> {
+        let cmd = Command::Boot;
+        let cmd = postcard::to_stdvec(&cmd).expect("Failed to serialize boot command");
+        self.write_frame(&cmd)
     }
 }
 
 fn main() {
     let args = Cli::parse();
-    let mut client = Client::new(
-        &args.hostname,
-        args.port,
-        args.timeout,
-        args.chunk_size as u32,
-    )
-    .expect("Failed to connect");
+
+    let config = match args.transport {
+        TransportKind::Tcp => {
+            let hostname = args
+                .hostname
+                .clone()
+                .expect("hostname is required for --transport tcp");
+
+            if args.boot_req {
+                println!("Sending UDP boot request to {}:{}", hostname, args.boot_req_port);
+                request_bootloader_entry(&hostname, args.boot_req_port, args.port, args.timeout)
+                    .expect("Failed to send boot request and reach the bootloader's TCP port");
+            }
+
+            TransportConfig::Tcp {
+                hostname,
+                port: args.port,
+            }
+        }
+        TransportKind::Serial => TransportConfig::Serial {
+            device: args
+                .device
+                .clone()
+                .expect("--device is required for --transport serial"),
+            baud: args.baud,
+        },
+    };
+
+    let mut client =
+        Client::connect(config, args.timeout, args.chunk_size as u32).expect("Failed to connect");
 
     match args.command {
         Commands::Info => {
-            println!("Info {:?}", client.send_info_request())
+            client.send_info_request().expect("Failed to send info request");
         }
-        Commands::Program { lma, binfile } => {
+        Commands::Program {
+            lma,
+            resume,
+            pubkey,
+            signature,
+            verify_on_device,
+            binfile,
+        } => {
+            let image = std::fs::read(&binfile).expect("Failed to read binfile for verification");
+            let pubkey = crypto::load_public_key(pubkey.as_deref())
+                .expect("Failed to load trusted public key");
+            let (payload, signature) = crypto::extract_signature(&image, signature.as_deref())
+                .expect("Failed to read firmware signature");
+            crypto::verify(payload, &pubkey, &signature)
+                .expect("Firmware signature verification failed, refusing to flash");
+            println!("Firmware signature verified, proceeding to erase and program");
+
+            // Flash exactly the bytes that were just verified, e.g. with an appended
+            // signature block already stripped off, instead of re-reading the raw file.
+            let payload = payload.to_vec();
+            let extension = binfile
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.to_string());
+
             client
-                .send_program_request(lma, binfile)
+                .send_program_request(
+                    lma,
+                    payload,
+                    extension,
+                    args.max_retries,
+                    resume,
+                    args.rate_limit,
+                    verify_on_device,
+                    args.window,
+                )
                 .expect("Failed to send program request");
+
+            if args.no_reboot {
+                println!("--no-reboot set, leaving the device in the bootloader");
+            } else {
+                client
+                    .send_boot_request()
+                    .expect("Failed to send boot command");
+            }
         }
         Commands::Configure {
             lma,
@@ -251,11 +703,8 @@ fn main() {
             //    .expect("Failed to send write command");
         }
         Commands::Boot => {
-            let cmd = Command::Boot;
-            let cmd = postcard::to_stdvec(&cmd).expect("Failed to serialize boot command");
             client
-                .socket
-                .write_all(&cmd)
+                .send_boot_request()
                 .expect("Failed to send boot command");
         }
         Commands::Erase => {
@@ -0,0 +1,51 @@
+//! Abstracts `Client` over the physical link to the bootloader. Every command is just a
+//! framed read/write, so TCP and UART backends can share all of `Client`'s logic.
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+/// Anything `Client` can frame commands over. TCP sockets and serial ports already
+/// implement `Read + Write`; this just lets `Client` hold either behind one trait object.
+pub trait Transport: Read + Write + Send {}
+
+impl Transport for TcpStream {}
+impl Transport for Box<dyn serialport::SerialPort> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TransportKind {
+    Tcp,
+    Serial,
+}
+
+/// Everything needed to (re)open a connection to the bootloader, kept around so `Client`
+/// can transparently reconnect after a dropped TCP socket or a serial read error.
+#[derive(Debug, Clone)]
+pub enum TransportConfig {
+    Tcp { hostname: String, port: u16 },
+    Serial { device: String, baud: u32 },
+}
+
+impl TransportConfig {
+    pub fn open(&self, timeout: u64) -> io::Result<Box<dyn Transport>> {
+        match self {
+            TransportConfig::Tcp { hostname, port } => {
+                let socket = TcpStream::connect((hostname.as_str(), *port))?;
+                socket.set_read_timeout(Some(Duration::from_secs(timeout)))?;
+                socket.set_write_timeout(Some(Duration::from_secs(timeout)))?;
+                //Just block for now as we only do one op at a time
+                socket.set_nonblocking(false)?;
+                Ok(Box::new(socket))
+            }
+            TransportConfig::Serial { device, baud } => {
+                let port = serialport::new(device.as_str(), *baud)
+                    .timeout(Duration::from_secs(timeout))
+                    .open()
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+                Ok(Box::new(port))
+            }
+        }
+    }
+}